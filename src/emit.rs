@@ -0,0 +1,51 @@
+use crate::{get_libs_and_paths, ConfigVariables};
+
+// The config variables whose link lines are emitted when no explicit
+// keys are supplied.
+pub const DEFAULT_LINK_KEYS: &[&str] = &["BLAS_LIBS", "LAPACK_LIBS", "LIBS"];
+
+// De-duplicate the entries of `items`, preserving the order of their
+// first occurrence.
+fn dedup_preserving_order(items: Vec<String>) -> Vec<String> {
+    let mut seen: Vec<String> = Vec::with_capacity(items.len());
+    for item in items {
+        if !seen.contains(&item) {
+            seen.push(item);
+        }
+    }
+    seen
+}
+
+/// Emit the Cargo linker directives implied by the named config variables.
+///
+/// Each key in `keys` is looked up in `configs`, its value run through
+/// [`get_libs_and_paths`], and the collected `-L`/`-l` tokens printed as
+/// `cargo:rustc-link-search=native=<path>` and `cargo:rustc-link-lib=<lib>`
+/// directives, with duplicates removed but first-seen order preserved. This
+/// is the glue a `build.rs` would otherwise have to write by hand to link R's
+/// numeric libraries, in the spirit of how the `cc` crate drives linking.
+///
+/// When `keys` is empty, [`DEFAULT_LINK_KEYS`] (`BLAS_LIBS`, `LAPACK_LIBS`,
+/// `LIBS`) is used.
+pub fn emit_cargo_link(configs: &ConfigVariables, keys: &[&str]) {
+    let keys = if keys.is_empty() {
+        DEFAULT_LINK_KEYS
+    } else {
+        keys
+    };
+
+    let values: Vec<String> = keys
+        .iter()
+        .filter_map(|key| configs.get(key))
+        .cloned()
+        .collect();
+
+    let flags = get_libs_and_paths(values);
+
+    for path in dedup_preserving_order(flags.search_paths) {
+        println!("cargo:rustc-link-search=native={}", path);
+    }
+    for lib in dedup_preserving_order(flags.libs) {
+        println!("cargo:rustc-link-lib={}", lib);
+    }
+}
@@ -1,11 +1,15 @@
 use std::{
     collections::HashMap,
+    env,
     ffi::{OsStr, OsString},
     io,
+    path::PathBuf,
     process::Command,
 };
 
-use cached::proc_macro::once;
+use cached::proc_macro::{cached, once};
+
+pub mod emit;
 
 #[cfg(target_family = "unix")]
 use std::os::unix::ffi::OsStrExt;
@@ -16,12 +20,84 @@ use std::os::windows::ffi::OsStringExt;
 #[derive(Debug, Clone)]
 pub struct ConfigVariables {
     map: HashMap<String, String>,
+    raw: OsString,
 }
 
 impl ConfigVariables {
     pub fn get(&self, key: &str) -> Option<&String> {
         self.map.get(key)
     }
+
+    /// The full captured `R CMD config --all` output, as an [`OsString`].
+    ///
+    /// Parsing may skip R's trailing `##` comment/override sections, but the
+    /// whole captured text — including anything past the comment marker — is
+    /// preserved here, so callers can inspect sections the map drops. This is
+    /// the post-decode string the invoker returned: on Unix it is the bytes
+    /// verbatim, and on all platforms it preserves more than the lossy
+    /// `to_string_lossy()` view used to build the map. (It is *not* the raw
+    /// pre-decode stdout bytes; on Windows those have already passed through
+    /// the console-code-page conversion before reaching this field.)
+    pub fn raw(&self) -> &OsStr {
+        self.raw.as_os_str()
+    }
+
+    // Fetch a plain scalar variable (e.g. a compiler name).
+    fn scalar(&self, key: &str) -> Option<String> {
+        self.map.get(key).cloned()
+    }
+
+    // Whitespace-split the value of a flag variable into its tokens.
+    fn tokens(&self, key: &str) -> Vec<String> {
+        self.map
+            .get(key)
+            .map(|value| value.split_whitespace().map(str::to_string).collect())
+            .unwrap_or_default()
+    }
+
+    // Run a link-line variable through the flag parser and return its `-l`
+    // libraries.
+    fn link_libs(&self, key: &str) -> Vec<String> {
+        match self.map.get(key) {
+            Some(value) => get_libs_and_paths(vec![value.clone()]).libs,
+            None => Vec::new(),
+        }
+    }
+
+    /// The C compiler (`CC`).
+    pub fn cc(&self) -> Option<String> {
+        self.scalar("CC")
+    }
+
+    /// The C++ compiler (`CXX`).
+    pub fn cxx(&self) -> Option<String> {
+        self.scalar("CXX")
+    }
+
+    /// The Fortran compiler (`FC`).
+    pub fn fc(&self) -> Option<String> {
+        self.scalar("FC")
+    }
+
+    /// The C compiler flags (`CFLAGS`), split into tokens.
+    pub fn cflags(&self) -> Vec<String> {
+        self.tokens("CFLAGS")
+    }
+
+    /// The BLAS libraries (`BLAS_LIBS`), as `-l` library names.
+    pub fn blas_libs(&self) -> Vec<String> {
+        self.link_libs("BLAS_LIBS")
+    }
+
+    /// The LAPACK libraries (`LAPACK_LIBS`), as `-l` library names.
+    pub fn lapack_libs(&self) -> Vec<String> {
+        self.link_libs("LAPACK_LIBS")
+    }
+
+    /// The R installation root (`R_HOME`).
+    pub fn r_home(&self) -> Option<String> {
+        self.scalar("R_HOME")
+    }
 }
 
 // frustratingly, something like the following does not exist in an
@@ -87,10 +163,37 @@ fn byte_array_to_os_string(bytes: &[u8]) -> OsString {
     OsString::from_wide(&wide)
 }
 
+/// Strategy for invoking `R CMD config --all` and capturing its output.
+///
+/// The default [`ProcessInvoker`] spawns a child process, but a caller may
+/// supply their own implementation — a mock returning canned text in a unit
+/// test, or an alternate execution strategy such as a remote host or sandbox.
+/// The trait/factory split mirrors the `os_access` abstraction used in the
+/// icedtea rust-launcher, where a `get_os` factory hands back the active
+/// implementation.
+pub trait RInvoker {
+    fn run_config(&self, binary: &OsStr) -> io::Result<OsString>;
+}
+
+/// The default [`RInvoker`], which runs `R CMD config --all` as a child
+/// process.
+pub struct ProcessInvoker;
+
+impl RInvoker for ProcessInvoker {
+    fn run_config(&self, binary: &OsStr) -> io::Result<OsString> {
+        r_cmd_config(binary)
+    }
+}
+
+/// Return the invoker used by the zero-argument entry points.
+pub fn get_invoker() -> ProcessInvoker {
+    ProcessInvoker
+}
+
 // Execute R CMD config and return the captured output
 fn r_cmd_config<S: AsRef<OsStr>>(r_binary: S) -> io::Result<OsString> {
     let out = Command::new(r_binary)
-        .args(&["CMD", "config", "--all"])
+        .args(["CMD", "config", "--all"])
         .output()?;
 
     // if there are any errors we print them out, helps with debugging
@@ -106,78 +209,285 @@ fn r_cmd_config<S: AsRef<OsStr>>(r_binary: S) -> io::Result<OsString> {
     Ok(byte_array_to_os_string(&out.stdout))
 }
 
+/// Determine which R binary to invoke.
+///
+/// Following the convention the `cc` crate uses for `CC`/`CXX`, an explicit
+/// `RCONFIG_R` override wins; failing that, `R_HOME` points at an R
+/// installation whose `bin/R` is used; otherwise we fall back to the bare
+/// name `R` and let `PATH` resolve it.
+fn resolve_r_binary() -> OsString {
+    if let Some(binary) = env::var_os("RCONFIG_R") {
+        return binary;
+    }
+    if let Some(r_home) = env::var_os("R_HOME") {
+        let mut path = PathBuf::from(r_home);
+        path.push("bin");
+        path.push("R");
+        return path.into_os_string();
+    }
+    OsString::from("R")
+}
+
 #[once]
 pub fn build_r_cmd_configs() -> ConfigVariables {
-    let r_configs = r_cmd_config("R");
+    build_r_cmd_configs_for(resolve_r_binary())
+}
+
+/// Build the config map for a specific R binary.
+///
+/// Results are memoized per binary, so different R installations do not
+/// collide in the cache the way the keyless [`build_r_cmd_configs`] would.
+pub fn build_r_cmd_configs_for(binary: impl AsRef<OsStr>) -> ConfigVariables {
+    build_r_cmd_configs_cached(binary.as_ref().to_os_string())
+}
+
+#[cached]
+fn build_r_cmd_configs_cached(binary: OsString) -> ConfigVariables {
+    build_r_cmd_configs_with_binary(&get_invoker(), &binary, false)
+}
+
+/// Build the config map using an injected [`RInvoker`].
+///
+/// This is the testable core of [`build_r_cmd_configs`]; pass a mock invoker
+/// to supply canned `R CMD config` output without a real R on `PATH`.
+pub fn build_r_cmd_configs_with(invoker: &dyn RInvoker) -> ConfigVariables {
+    build_r_cmd_configs_with_options(invoker, false)
+}
+
+/// Build the config map using an injected [`RInvoker`], opting in to
+/// truncation at R's `##` comment marker.
+///
+/// The default [`build_r_cmd_configs_with`] keeps the trailing comment/override
+/// sections; pass `truncate_at_comments = true` to restore the historical
+/// behavior of stopping at the first `##` line.
+pub fn build_r_cmd_configs_with_options(
+    invoker: &dyn RInvoker,
+    truncate_at_comments: bool,
+) -> ConfigVariables {
+    build_r_cmd_configs_with_binary(invoker, &resolve_r_binary(), truncate_at_comments)
+}
 
+fn build_r_cmd_configs_with_binary(
+    invoker: &dyn RInvoker,
+    binary: &OsStr,
+    truncate_at_comments: bool,
+) -> ConfigVariables {
+    let raw = invoker.run_config(binary).unwrap_or_default();
+    parse_config_variables(raw, truncate_at_comments)
+}
+
+/// Parse captured `R CMD config` output into a [`ConfigVariables`].
+///
+/// When `truncate_at_comments` is set, parsing stops at the first line
+/// beginning with R's `##` comment marker (the historical behavior). The
+/// complete captured text is retained on [`ConfigVariables::raw`] either way,
+/// so nothing from the interpreter is lost.
+fn parse_config_variables(raw: OsString, truncate_at_comments: bool) -> ConfigVariables {
     let mut rcmd_config_map = HashMap::new();
-    match r_configs {
-        Ok(configs) => {
-            let input = configs.as_os_str().to_string_lossy();
-            for line in input.lines() {
-                // Ignore lines beyond comment marker
-                if line.starts_with("##") {
+    {
+        let input = raw.as_os_str().to_string_lossy();
+        for line in input.lines() {
+            // Comment/override lines never form valid assignments. Stop at
+            // them when truncation was requested; otherwise just skip them so
+            // a stray `=` inside a comment can't become a bogus map entry.
+            if line.starts_with("##") {
+                if truncate_at_comments {
                     break;
                 }
-                let parts: Vec<_> = line.split('=').map(str::trim).collect();
-                if let [name, value] = parts.as_slice() {
-                    rcmd_config_map.insert(name.to_string(), value.to_string());
-                }
+                continue;
+            }
+            // Split on the first `=` only, so values that themselves contain
+            // `=` (e.g. `CFLAGS = -mtune=native`) are kept intact.
+            let mut parts = line.splitn(2, '=');
+            if let (Some(name), Some(value)) = (parts.next(), parts.next()) {
+                rcmd_config_map.insert(name.trim().to_string(), value.trim().to_string());
             }
         }
-        _ => (),
     }
     // Return the struct
     ConfigVariables {
         map: rcmd_config_map,
+        raw,
     }
 }
 
-pub fn get_libs_and_paths(strings: Vec<String>) -> (Vec<String>, Vec<String>) {
-    let mut paths: Vec<String> = Vec::new();
-    let mut libs: Vec<String> = Vec::new();
+/// The tokens extracted from a collection of R config link/compile lines.
+///
+/// R's config strings (CPPFLAGS, PKG_CFLAGS, the macOS link lines) mix several
+/// flavours of flag, so each recognized kind gets its own field.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ParsedFlags {
+    /// `-L` library search directories.
+    pub search_paths: Vec<String>,
+    /// `-l` libraries to link.
+    pub libs: Vec<String>,
+    /// `-I` include directories.
+    pub include_paths: Vec<String>,
+    /// `-framework Foo` frameworks (macOS).
+    pub frameworks: Vec<String>,
+    /// `-Wl,-rpath,<dir>` runtime search directories.
+    pub rpaths: Vec<String>,
+    /// `-D` preprocessor defines, as `NAME` or `NAME=val`.
+    pub defines: Vec<String>,
+}
+
+pub fn get_libs_and_paths(strings: Vec<String>) -> ParsedFlags {
+    let mut flags = ParsedFlags::default();
 
     for s in &strings {
         let parts: Vec<&str> = s.split_whitespace().collect();
-        for part in parts {
-            if part.starts_with("-L") {
-                paths.push(part[2..].to_string());
-            } else if part.starts_with("-l") {
-                libs.push(part[2..].to_string());
+        let mut i = 0;
+        while i < parts.len() {
+            let part = parts[i];
+            if let Some(path) = part.strip_prefix("-L") {
+                flags.search_paths.push(path.to_string());
+            } else if let Some(lib) = part.strip_prefix("-l") {
+                flags.libs.push(lib.to_string());
+            } else if let Some(inc) = part.strip_prefix("-I") {
+                flags.include_paths.push(inc.to_string());
+            } else if part == "-framework" {
+                // `-framework` takes its value as the *next* token.
+                if let Some(name) = parts.get(i + 1) {
+                    flags.frameworks.push(name.to_string());
+                    i += 1;
+                }
+            } else if let Some(rpath) = part.strip_prefix("-Wl,-rpath,") {
+                for dir in rpath.split(',') {
+                    flags.rpaths.push(dir.to_string());
+                }
+            } else if let Some(def) = part.strip_prefix("-D") {
+                flags.defines.push(def.to_string());
             }
+            i += 1;
         }
     }
-    (paths, libs)
+
+    flags
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     
+    // A mock invoker returning canned `R CMD config` text, so the parser can
+    // be exercised on machines without R installed.
+    struct MockInvoker {
+	output: OsString,
+    }
+
+    impl RInvoker for MockInvoker {
+	fn run_config(&self, _binary: &OsStr) -> io::Result<OsString> {
+	    Ok(self.output.clone())
+	}
+    }
+
+    #[test]
+    fn build_from_mock_invoker() {
+	let invoker = MockInvoker {
+	    output: OsString::from("CC = gcc\nFC = gfortran\n"),
+	};
+	let r_configs = build_r_cmd_configs_with(&invoker);
+	assert_eq!(r_configs.get("CC").map(String::as_str), Some("gcc"));
+	assert_eq!(r_configs.get("FC").map(String::as_str), Some("gfortran"));
+    }
+
+    #[test]
+    fn raw_output_is_preserved() {
+	let text = "CC = gcc\n## Variables for packages\nPKG = value\n";
+	let invoker = MockInvoker {
+	    output: OsString::from(text),
+	};
+	let r_configs = build_r_cmd_configs_with(&invoker);
+	// Nothing is truncated by default, so the section past `##` is kept.
+	assert_eq!(r_configs.get("PKG").map(String::as_str), Some("value"));
+	assert_eq!(r_configs.raw(), OsStr::new(text));
+    }
+
+    #[test]
+    fn comment_lines_are_not_parsed_as_assignments() {
+	let invoker = MockInvoker {
+	    output: OsString::from("CC = gcc\n## foo = bar\n"),
+	};
+	let r_configs = build_r_cmd_configs_with(&invoker);
+	assert_eq!(r_configs.get("## foo"), None);
+	assert_eq!(r_configs.get("CC").map(String::as_str), Some("gcc"));
+    }
+
+    #[test]
+    fn truncation_is_opt_in() {
+	let invoker = MockInvoker {
+	    output: OsString::from("CC = gcc\n## Variables\nPKG = value\n"),
+	};
+	let r_configs = build_r_cmd_configs_with_options(&invoker, true);
+	// With truncation on, nothing past the `##` marker is parsed.
+	assert_eq!(r_configs.get("PKG"), None);
+	assert_eq!(r_configs.get("CC").map(String::as_str), Some("gcc"));
+    }
+
+    // A small but representative `R CMD config --all` excerpt, so the
+    // accessor tests run without a real R on PATH.
+    fn sample_invoker() -> MockInvoker {
+	MockInvoker {
+	    output: OsString::from(concat!(
+		"CC = gcc\n",
+		"FC = gfortran\n",
+		"BLAS_LIBS = -L/usr/lib -lblas\n",
+		"LAPACK_LIBS = -L/usr/lib -llapack\n",
+	    )),
+	}
+    }
+
+    #[test]
+    fn cflags_with_equals_survive() {
+	let invoker = MockInvoker {
+	    output: OsString::from("CFLAGS = -g -O2 -mtune=native -fmessage-length=0\n"),
+	};
+	let r_configs = build_r_cmd_configs_with(&invoker);
+	assert_eq!(
+	    r_configs.cflags(),
+	    vec!["-g", "-O2", "-mtune=native", "-fmessage-length=0"]
+	);
+    }
+
     #[test]
     fn get_cc() {
-	let r_configs = build_r_cmd_configs();
+	let r_configs = build_r_cmd_configs_with(&sample_invoker());
 	let value = r_configs.get("CC").expect("Unexpected missing value for R CMD config CC");
 	assert!(!value.to_owned().is_empty(), "Value is empty");
     }
 
     #[test]
     fn get_fc() {
-	let r_configs = build_r_cmd_configs();
+	let r_configs = build_r_cmd_configs_with(&sample_invoker());
 	let value = r_configs.map.get("FC").expect("Unexpected missing value for R CMD config FC");
 	assert!(!value.to_owned().is_empty(), "Value is empty");
     }
-    
+
     #[test]
     fn get_blas_and_lapack() {
-	let r_configs = build_r_cmd_configs();	
+	let r_configs = build_r_cmd_configs_with(&sample_invoker());
 	let blas_libs = r_configs.get("BLAS_LIBS").expect("Unexpected missing value for R CMD config BLAS_LIBS").to_owned();
-	let (_, lib) = get_libs_and_paths([ blas_libs ].to_vec());
-	assert!(!lib.is_empty(), "Unexpected empty BLAS library");
+	let flags = get_libs_and_paths([ blas_libs ].to_vec());
+	assert!(!flags.libs.is_empty(), "Unexpected empty BLAS library");
 
 	let lapack_libs = r_configs.get("LAPACK_LIBS").expect("Unexpected missing value for R CMD config LAPACK_LIBS").to_owned();
-	let (_, lib) = get_libs_and_paths([ lapack_libs ].to_vec());
-	assert!(!lib.is_empty(), "Unexpected empty LAPACK library");
+	let flags = get_libs_and_paths([ lapack_libs ].to_vec());
+	assert!(!flags.libs.is_empty(), "Unexpected empty LAPACK library");
+    }
+
+    #[test]
+    fn parse_mixed_flags() {
+	let line = String::from(
+	    "-I/opt/R/include -L/usr/lib -lR -framework Accelerate \
+	     -Wl,-rpath,/opt/R/lib -DNDEBUG -DVERSION=2",
+	);
+	let flags = get_libs_and_paths(vec![line]);
+	assert_eq!(flags.include_paths, vec!["/opt/R/include"]);
+	assert_eq!(flags.search_paths, vec!["/usr/lib"]);
+	assert_eq!(flags.libs, vec!["R"]);
+	assert_eq!(flags.frameworks, vec!["Accelerate"]);
+	assert_eq!(flags.rpaths, vec!["/opt/R/lib"]);
+	assert_eq!(flags.defines, vec!["NDEBUG", "VERSION=2"]);
     }
 }
 